@@ -0,0 +1,77 @@
+use gtk::subclass::prelude::*;
+use gtk::{glib, prelude::*};
+
+mod imp {
+    use std::cell::{Cell, OnceCell};
+
+    use super::*;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate, glib::Properties)]
+    #[properties(wrapper_type = super::SymbolItem)]
+    #[template(resource = "/fyi/zoey/TeX-Match/ui/symbol_item.ui")]
+    pub struct SymbolItem {
+        #[template_child]
+        pub preview: TemplateChild<gtk::DrawingArea>,
+        #[template_child]
+        pub command_label: TemplateChild<gtk::Label>,
+        pub symbol: OnceCell<detexify::Symbol>,
+        #[property(get, set)]
+        pub score: Cell<f32>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SymbolItem {
+        const NAME: &'static str = "SymbolItem";
+        type Type = super::SymbolItem;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for SymbolItem {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            if let Some(symbol) = self.symbol.get() {
+                self.command_label.set_label(&format!("\\{}", symbol.id()));
+            }
+        }
+    }
+
+    impl WidgetImpl for SymbolItem {}
+    impl BoxImpl for SymbolItem {}
+}
+
+glib::wrapper! {
+    pub struct SymbolItem(ObjectSubclass<imp::SymbolItem>)
+        @extends gtk::Widget, gtk::Box;
+}
+
+impl SymbolItem {
+    pub fn new(symbol: detexify::Symbol) -> Self {
+        let obj: Self = glib::Object::builder().build();
+        obj.imp().symbol.set(symbol).expect("Failed to set symbol");
+        obj.imp()
+            .command_label
+            .set_label(&format!("\\{}", obj.symbol().id()));
+        obj
+    }
+
+    /// Returns the `detexify::Symbol` this item represents.
+    pub fn symbol(&self) -> &detexify::Symbol {
+        self.imp().symbol.get().expect("Symbol not set")
+    }
+
+    /// Returns the drawing area rendering just the symbol preview, without the
+    /// surrounding `\command` label.
+    pub fn preview(&self) -> &gtk::DrawingArea {
+        &self.imp().preview
+    }
+}
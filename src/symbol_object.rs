@@ -0,0 +1,42 @@
+use gtk::glib;
+use gtk::subclass::prelude::*;
+
+mod imp {
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+
+    #[derive(Debug, Default, glib::Properties)]
+    #[properties(wrapper_type = super::SymbolObject)]
+    pub struct SymbolObject {
+        pub id: RefCell<String>,
+        #[property(get, set)]
+        pub score: Cell<f32>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SymbolObject {
+        const NAME: &'static str = "SymbolObject";
+        type Type = super::SymbolObject;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for SymbolObject {}
+}
+
+glib::wrapper! {
+    pub struct SymbolObject(ObjectSubclass<imp::SymbolObject>);
+}
+
+impl SymbolObject {
+    pub fn new(id: String) -> Self {
+        let obj: Self = glib::Object::builder().property("score", f32::NEG_INFINITY).build();
+        obj.imp().id.replace(id);
+        obj
+    }
+
+    /// Returns the `detexify::Symbol` id this object refers to.
+    pub fn id(&self) -> String {
+        self.imp().id.borrow().clone()
+    }
+}
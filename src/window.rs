@@ -1,16 +1,33 @@
+use std::collections::HashMap;
 use std::time::Instant;
 
+use adw::prelude::*;
 use gtk::subclass::prelude::*;
-use gtk::{gio, glib};
-use gtk::{prelude::*, StringObject};
+use gtk::{gdk, gio, glib};
 use itertools::Itertools;
 
 use crate::application::TexApplication;
 use crate::config::PROFILE;
 use crate::symbol_item::SymbolItem;
+use crate::symbol_object::SymbolObject;
+
+/// A classification request stamped with a monotonically increasing id, so stale
+/// results (superseded by a newer request before they arrive) can be told apart from
+/// the latest one.
+enum ClassifierRequest {
+    Classify {
+        id: u64,
+        strokes: Vec<detexify::Stroke>,
+    },
+}
+
+struct ClassifierResult {
+    id: u64,
+    scores: Vec<detexify::Score>,
+}
 
 mod imp {
-    use std::cell::{OnceCell, RefCell};
+    use std::cell::{Cell, OnceCell, RefCell};
 
     use super::*;
 
@@ -21,11 +38,21 @@ mod imp {
         pub drawing_area: TemplateChild<gtk::DrawingArea>,
         #[template_child]
         pub symbol_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub search_entry: TemplateChild<gtk::SearchEntry>,
         pub surface: RefCell<Option<cairo::ImageSurface>>,
         pub symbols: OnceCell<gio::ListStore>,
+        pub sorter: OnceCell<gtk::CustomSorter>,
+        pub filter: OnceCell<gtk::CustomFilter>,
         pub strokes: RefCell<Vec<detexify::Stroke>>,
+        pub redo_stack: RefCell<Vec<detexify::Stroke>>,
         pub current_stroke: RefCell<detexify::Stroke>,
-        pub sender: OnceCell<std::sync::mpsc::Sender<Vec<detexify::Stroke>>>,
+        pub sender: OnceCell<std::sync::mpsc::Sender<super::ClassifierRequest>>,
+        pub next_request_id: Cell<u64>,
+        pub latest_request_id: Cell<u64>,
+        pub classify_debounce: RefCell<Option<glib::SourceId>>,
     }
 
     #[glib::object_subclass]
@@ -58,6 +85,8 @@ mod imp {
             obj.setup_symbol_list();
             obj.setup_drawing_area();
             obj.setup_classifier();
+            obj.setup_search();
+            obj.setup_actions();
         }
 
         fn dispose(&self) {
@@ -89,13 +118,8 @@ impl TeXMatchWindow {
     }
 
     fn setup_symbol_list(&self) {
-        let mut model = gio::ListStore::new::<gtk::StringObject>();
-        model.extend(
-            detexify::iter_symbols()
-                .map(|sym| sym.id())
-                .map(gtk::StringObject::new),
-        );
-        // let model: gtk::StringList = detexify::iter_symbols().map(|symbol| symbol.id()).collect();
+        let model = gio::ListStore::new::<SymbolObject>();
+        model.extend(detexify::iter_symbols().map(|sym| SymbolObject::new(sym.id())));
         tracing::debug!("Loaded {} symbols", model.n_items());
 
         self.imp()
@@ -103,17 +127,88 @@ impl TeXMatchWindow {
             .set(model.clone())
             .expect("Failed to set symbol model");
 
-        let selection_model = gtk::NoSelection::new(Some(model));
+        // Sort by descending score so the best match is always on top. Re-sorting in
+        // place (instead of rebuilding the model) lets GTK diff the reorder rather than
+        // swapping out every item, which is too slow for ~1000 symbols.
+        let sorter = gtk::CustomSorter::new(move |a, b| {
+            let score_a = a
+                .downcast_ref::<SymbolObject>()
+                .expect("The object is not of type `SymbolObject`.")
+                .score();
+            let score_b = b
+                .downcast_ref::<SymbolObject>()
+                .expect("The object is not of type `SymbolObject`.")
+                .score();
+            score_b.partial_cmp(&score_a).unwrap_or(std::cmp::Ordering::Equal).into()
+        });
+        let sorted_model = gtk::SortListModel::new(Some(model), Some(sorter.clone()));
+        self.imp().sorter.set(sorter).expect("Failed to set sorter");
+
+        // While searching, only symbols the query actually matches should be shown;
+        // outside of a search every symbol stays visible, as before.
+        let filter = gtk::CustomFilter::new(glib::clone!(@weak self as window => @default-return false, move |obj| {
+            if !window.is_searching() {
+                return true;
+            }
+            let symbol_object = obj.downcast_ref::<SymbolObject>().expect("The object is not of type `SymbolObject`.");
+            symbol_object.score() > f32::NEG_INFINITY
+        }));
+        let filtered_model = gtk::FilterListModel::new(Some(sorted_model), Some(filter.clone()));
+        self.imp().filter.set(filter).expect("Failed to set filter");
+
+        let selection_model = gtk::NoSelection::new(Some(filtered_model));
         self.imp().symbol_list.bind_model(
             Some(&selection_model),
             glib::clone!(@weak self as window => @default-panic, move |obj| {
-                let symbol_object = obj.downcast_ref::<StringObject>().expect("The object is not of type `StringObject`.");
-                let symbol_item = SymbolItem::new(detexify::Symbol::from_id(symbol_object.string().as_str()).expect("Failed to get symbol"));
+                let symbol_object = obj.downcast_ref::<SymbolObject>().expect("The object is not of type `SymbolObject`.");
+                let symbol_item = SymbolItem::new(detexify::Symbol::from_id(&symbol_object.id()).expect("Failed to get symbol"));
+                symbol_object
+                    .bind_property("score", &symbol_item, "score")
+                    .sync_create()
+                    .build();
+
+                let drag_source = gtk::DragSource::new();
+                drag_source.connect_prepare(
+                    glib::clone!(@weak symbol_item => @default-return None, move |_source, _x, _y| {
+                        let command = symbol_item.symbol().command();
+                        Some(gdk::ContentProvider::for_value(&command.to_value()))
+                    }),
+                );
+                drag_source.connect_drag_begin(
+                    glib::clone!(@weak symbol_item => move |source, _drag| {
+                        if let Some(paintable) = gtk::WidgetPaintable::new(Some(symbol_item.preview())).current_image() {
+                            source.set_icon(Some(&paintable), 0, 0);
+                        }
+                    }),
+                );
+                symbol_item.add_controller(drag_source);
+
                 symbol_item.upcast()
             }),
         );
 
         self.imp().symbol_list.set_visible(true);
+
+        self.imp().symbol_list.connect_row_activated(
+            glib::clone!(@weak self as window => move |_list, row| {
+                let symbol_item = row
+                    .child()
+                    .and_downcast::<SymbolItem>()
+                    .expect("Row child is not a `SymbolItem`");
+                window.copy_to_clipboard(symbol_item.symbol());
+            }),
+        );
+    }
+
+    /// Copies `symbol`'s LaTeX command to the clipboard and shows a confirmation toast.
+    fn copy_to_clipboard(&self, symbol: &detexify::Symbol) {
+        self.clipboard().set_text(&symbol.command());
+
+        let toast = adw::Toast::builder()
+            .title(format!("Copied \u{201c}{}\u{201d} to clipboard", symbol.command()))
+            .timeout(2)
+            .build();
+        self.imp().toast_overlay.add_toast(toast);
     }
 
     fn setup_classifier(&self) {
@@ -125,7 +220,7 @@ impl TeXMatchWindow {
             let classifier = detexify::Classifier::default();
 
             loop {
-                let Some(strokes) = req_rx.iter().next() else {
+                let Some(ClassifierRequest::Classify { id, strokes }) = req_rx.iter().next() else {
                     //channel has hung up, cleanly exit
                     tracing::info!("Exiting classifier thread");
                     return;
@@ -150,40 +245,179 @@ impl TeXMatchWindow {
                 };
 
                 res_tx
-                    .send_blocking(classifications.unwrap_or_default())
+                    .send_blocking(ClassifierResult {
+                        id,
+                        scores: classifications.unwrap_or_default(),
+                    })
                     .expect("Failed to send classifications");
             }
         });
 
         glib::spawn_future_local(glib::clone!(@weak self as window => async move {
             tracing::debug!("Listening for classifications");
-            while let Ok(classifications) = res_rx.recv().await {
+            while let Ok(result) = res_rx.recv().await {
+                if result.id < window.imp().latest_request_id.get() {
+                    tracing::trace!("Dropping stale classification result {}", result.id);
+                    continue;
+                }
+                if window.is_searching() {
+                    tracing::trace!("Dropping classification result while a search is active");
+                    continue;
+                }
+
+                let scores: HashMap<String, f32> = result
+                    .scores
+                    .into_iter()
+                    .map(|score| (score.id, score.score as f32))
+                    .collect();
+                window.apply_scores(&scores);
+            }
+        }));
+    }
+
+    /// Updates every symbol's `score` property from `scores` (missing ids sink to the
+    /// bottom via `f32::NEG_INFINITY`) and re-sorts/re-filters the list in place, so
+    /// only the items whose rank actually changed are redrawn.
+    fn apply_scores(&self, scores: &HashMap<String, f32>) {
+        for item in self.symbols().iter::<SymbolObject>() {
+            let item = item.expect("Failed to get `SymbolObject`");
+            let score = scores.get(&item.id()).copied().unwrap_or(f32::NEG_INFINITY);
+            item.set_score(score);
+        }
 
-                let symbols = window.symbols();
-                symbols.remove_all();
+        self.imp()
+            .sorter
+            .get()
+            .expect("Failed to get sorter")
+            .changed(gtk::SorterChange::Different);
+        self.imp()
+            .filter
+            .get()
+            .expect("Failed to get filter")
+            .changed(gtk::FilterChange::Different);
+    }
+
+    /// Whether the symbol list is currently being filtered by a search query. This is
+    /// derived from the search box and canvas state on every call, rather than cached in
+    /// a flag, so it can never go stale when the canvas changes out from under it (e.g.
+    /// the user starts drawing without touching the search box again).
+    fn is_searching(&self) -> bool {
+        !self.imp().search_entry.text().is_empty() && self.canvas_is_empty()
+    }
+
+    fn canvas_is_empty(&self) -> bool {
+        self.imp().strokes.borrow().is_empty()
+            && self.imp().current_stroke.borrow().points().next().is_none()
+    }
+
+    /// Re-evaluates the symbol list filter against the current `is_searching()` state,
+    /// without touching any symbol's score. Call this whenever the canvas transitions
+    /// between empty and non-empty, so search results hide/reappear as drawing starts
+    /// and stops even though the search box itself wasn't touched.
+    fn refresh_search_filter(&self) {
+        self.imp()
+            .filter
+            .get()
+            .expect("Failed to get filter")
+            .changed(gtk::FilterChange::Different);
+    }
 
-                // let objs = classifications.iter().map(|score|gtk::StringObject::new(&score.id)).collect_vec();
-                // symbols.extend_from_slice(&objs);
+    fn setup_search(&self) {
+        self.imp().search_entry.connect_search_changed(
+            glib::clone!(@weak self as window => move |entry| {
+                let query = entry.text().to_lowercase();
 
-                // swicthing out all 1k symbols takes too long, so only display the first 25
-                // TODO: find faster ways and display all
-                for symbol in classifications.iter().take(25) {
-                    symbols.append(&gtk::StringObject::new(&symbol.id))
+                if query.is_empty() || !window.canvas_is_empty() {
+                    window.refresh_search_filter();
+                    return;
                 }
-            }
-        }));
+
+                let scores: HashMap<String, f32> = detexify::iter_symbols()
+                    .filter_map(|symbol| {
+                        fuzzy_match(&query, &symbol.id()).map(|score| (symbol.id(), score as f32))
+                    })
+                    .collect();
+
+                window.apply_scores(&scores);
+            }),
+        );
     }
 
     fn classify(&self) {
         let imp = self.imp();
         let strokes = imp.strokes.borrow().clone();
+
+        let id = imp.next_request_id.get();
+        imp.next_request_id.set(id + 1);
+        imp.latest_request_id.set(id);
+
         imp.sender
             .get()
             .unwrap()
-            .send(strokes)
+            .send(ClassifierRequest::Classify { id, strokes })
             .expect("Failed to send strokes");
     }
 
+    /// Schedules a `classify()` call in ~100ms, coalescing rapid `drag-update` events
+    /// into a single request instead of firing one per pointer movement.
+    fn classify_debounced(&self) {
+        let imp = self.imp();
+        if let Some(source) = imp.classify_debounce.take() {
+            source.remove();
+        }
+
+        let source_id = glib::source::timeout_add_local_once(
+            std::time::Duration::from_millis(100),
+            glib::clone!(@weak self as window => move || {
+                window.imp().classify_debounce.take();
+                window.classify();
+            }),
+        );
+        imp.classify_debounce.replace(Some(source_id));
+    }
+
+    fn setup_actions(&self) {
+        let undo_action = gio::SimpleAction::new("undo", None);
+        undo_action.connect_activate(glib::clone!(@weak self as window => move |_, _| {
+            window.undo();
+        }));
+        self.add_action(&undo_action);
+
+        let redo_action = gio::SimpleAction::new("redo", None);
+        redo_action.connect_activate(glib::clone!(@weak self as window => move |_, _| {
+            window.redo();
+        }));
+        self.add_action(&redo_action);
+
+        let app = self.application().expect("Failed to get application");
+        app.set_accels_for_action("win.undo", &["<Control>z"]);
+        app.set_accels_for_action("win.redo", &["<Control><Shift>z"]);
+    }
+
+    /// Undoes the last committed stroke, pushing it onto the redo stack.
+    fn undo(&self) {
+        let Some(stroke) = self.imp().strokes.borrow_mut().pop() else {
+            return;
+        };
+        self.imp().redo_stack.borrow_mut().push(stroke);
+
+        self.refresh_search_filter();
+        self.classify();
+        self.imp().drawing_area.queue_draw();
+    }
+
+    /// Redoes the most recently undone stroke.
+    fn redo(&self) {
+        let Some(stroke) = self.imp().redo_stack.borrow_mut().pop() else {
+            return;
+        };
+        self.imp().strokes.borrow_mut().push(stroke);
+
+        self.refresh_search_filter();
+        self.classify();
+        self.imp().drawing_area.queue_draw();
+    }
+
     fn create_surface(&self, width: i32, height: i32) {
         let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)
             .expect("Failed to create surface");
@@ -205,6 +439,7 @@ impl TeXMatchWindow {
             glib::clone!(@weak self as window => move |_drag: &gtk::GestureDrag, x: f64, y: f64 | {
                 tracing::trace!("Drag started at {},{}", x, y);
                 window.imp().current_stroke.borrow_mut().add_point(detexify::Point {x, y});
+                window.refresh_search_filter();
                 window.imp().drawing_area.queue_draw();
             }),
         );
@@ -215,19 +450,23 @@ impl TeXMatchWindow {
                 //x,y refers to movements relative to start coord
                 let detexify::Point {x: prev_x, y: prev_y} = stroke.points().next().copied().unwrap();
                 stroke.add_point(detexify::Point {x: prev_x + x, y: prev_y + y});
+                drop(stroke);
                 window.imp().drawing_area.queue_draw();
+                window.classify_debounced();
             }),
         );
 
         drag.connect_drag_end(
             glib::clone!(@weak self as window => move |_drag: &gtk::GestureDrag, x: f64, y: f64 | {
                 tracing::trace!("Drag end at {},{}", x, y);
+                if let Some(source) = window.imp().classify_debounce.take() {
+                    source.remove();
+                }
                 let stroke = window.imp().current_stroke.take();
                 window.imp().strokes.borrow_mut().push(stroke);
+                window.imp().redo_stack.borrow_mut().clear();
                 window.imp().drawing_area.queue_draw();
-                //TODO: trigger classifier
                 window.classify();
-
             }),
         );
         imp.drawing_area.add_controller(drag);
@@ -273,8 +512,104 @@ impl TeXMatchWindow {
 
         //clear previous strokes
         self.imp().strokes.borrow_mut().clear();
+        self.imp().redo_stack.borrow_mut().clear();
         self.imp().current_stroke.borrow_mut().clear();
 
+        self.refresh_search_filter();
         self.imp().drawing_area.queue_draw();
+        self.classify();
+    }
+}
+
+/// Scores how well the lowercased `query` fuzzy-matches `candidate` as a subsequence.
+///
+/// Every character of `query` must occur in `candidate`, in order, but not necessarily
+/// contiguously. Consecutive matches and matches right after a separator (`\`, `{`, or
+/// the start of the string) are rewarded, while gaps between matches are penalized.
+/// Returns `None` if `query` isn't a subsequence of `candidate`.
+fn fuzzy_match(query: &str, candidate: &str) -> Option<i32> {
+    const CONSECUTIVE_BONUS: i32 = 8;
+    const SEPARATOR_BONUS: i32 = 6;
+    const GAP_PENALTY: i32 = 1;
+
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let candidate_chars: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut query_chars = query.chars();
+    let mut next_query_char = query_chars.next();
+
+    let mut score = 0;
+    let mut last_match_index: Option<usize> = None;
+
+    for (index, &ch) in candidate_chars.iter().enumerate() {
+        let Some(query_char) = next_query_char else {
+            break;
+        };
+
+        if ch != query_char {
+            continue;
+        }
+
+        let at_separator_start = index == 0 || matches!(candidate_chars[index - 1], '\\' | '{');
+        match last_match_index {
+            Some(previous) if previous + 1 == index => score += CONSECUTIVE_BONUS,
+            Some(previous) => score -= GAP_PENALTY * (index - previous - 1) as i32,
+            None => {}
+        }
+        if at_separator_start {
+            score += SEPARATOR_BONUS;
+        }
+
+        last_match_index = Some(index);
+        next_query_char = query_chars.next();
+    }
+
+    if next_query_char.is_some() {
+        return None;
+    }
+
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fuzzy_match;
+
+    #[test]
+    fn consecutive_match_scores_higher_than_a_gapped_one() {
+        let consecutive = fuzzy_match("int", "int").expect("should match");
+        let gapped = fuzzy_match("ac", "abc").expect("should match");
+
+        assert!(consecutive > gapped);
+    }
+
+    #[test]
+    fn match_right_after_a_separator_scores_higher() {
+        let after_separator = fuzzy_match("l", "\\l").expect("should match");
+        let mid_word = fuzzy_match("l", "xl").expect("should match");
+
+        assert!(after_separator > mid_word);
+    }
+
+    #[test]
+    fn gap_between_matches_is_penalized() {
+        let tight = fuzzy_match("ab", "ab").expect("should match");
+        let gapped = fuzzy_match("ab", "axb").expect("should match");
+
+        assert!(gapped < tight);
+    }
+
+    #[test]
+    fn non_subsequence_does_not_match() {
+        assert_eq!(fuzzy_match("z", "abc"), None);
+        assert_eq!(fuzzy_match("ba", "abc"), None);
+    }
+
+    #[test]
+    fn empty_query_matches_everything_with_a_zero_score() {
+        assert_eq!(fuzzy_match("", "alpha"), Some(0));
+        assert_eq!(fuzzy_match("", ""), Some(0));
     }
 }